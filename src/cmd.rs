@@ -1,11 +1,617 @@
 use std::{
-    io::{Error, ErrorKind},
+    collections::HashMap,
+    fmt,
+    io::{Error, ErrorKind, Read},
     path::{Path, PathBuf},
-    process::{Command, Output},
+    process::{Command, Output, Stdio},
+    sync::RwLock,
+    thread,
+    time::Duration,
 };
 
 use which::which;
 
+/// A structured error produced when running a [`Cmd`] fails, either because
+/// the child process could not be spawned or because it exited with a
+/// non-zero status.
+///
+/// Unlike a bare `io::Error`, this embeds the full reconstructed command
+/// line and the working directory the command was run in, so the failure
+/// can be diagnosed without re-running it.
+#[derive(Debug)]
+pub struct CmdError {
+    /// The program and arguments, joined the way they were invoked.
+    pub command: String,
+    /// The working directory the command was run in.
+    pub cwd: PathBuf,
+    kind: CmdErrorKind,
+}
+
+#[derive(Debug)]
+enum CmdErrorKind {
+    Spawn(Error),
+    ExitStatus(Option<i32>),
+    TimedOut(Duration),
+}
+
+impl CmdError {
+    fn spawn(command: String, cwd: PathBuf, err: Error) -> Self {
+        CmdError {
+            command,
+            cwd,
+            kind: CmdErrorKind::Spawn(err),
+        }
+    }
+
+    fn exit_status(command: String, cwd: PathBuf, code: Option<i32>) -> Self {
+        CmdError {
+            command,
+            cwd,
+            kind: CmdErrorKind::ExitStatus(code),
+        }
+    }
+
+    fn timed_out(command: String, cwd: PathBuf, timeout: Duration) -> Self {
+        CmdError {
+            command,
+            cwd,
+            kind: CmdErrorKind::TimedOut(timeout),
+        }
+    }
+
+    /// Whether this error was caused by the command exceeding its deadline,
+    /// as opposed to a spawn failure or a non-zero exit.
+    pub fn is_timed_out(&self) -> bool {
+        matches!(self.kind, CmdErrorKind::TimedOut(_))
+    }
+}
+
+impl fmt::Display for CmdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            CmdErrorKind::Spawn(err) => write!(
+                f,
+                "failed to spawn `{}` in `{}`: {}",
+                self.command,
+                self.cwd.display(),
+                err
+            ),
+            CmdErrorKind::ExitStatus(Some(code)) => write!(
+                f,
+                "command `{}` in `{}` exited with status {}",
+                self.command,
+                self.cwd.display(),
+                code
+            ),
+            CmdErrorKind::ExitStatus(None) => write!(
+                f,
+                "command `{}` in `{}` was terminated by a signal",
+                self.command,
+                self.cwd.display()
+            ),
+            CmdErrorKind::TimedOut(timeout) => write!(
+                f,
+                "command `{}` in `{}` timed out after {:?} and was killed",
+                self.command,
+                self.cwd.display(),
+                timeout
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CmdError {}
+
+impl From<CmdError> for Error {
+    fn from(err: CmdError) -> Self {
+        let kind = if err.is_timed_out() {
+            ErrorKind::TimedOut
+        } else {
+            ErrorKind::Other
+        };
+        Error::new(kind, err)
+    }
+}
+
+/// A builder for running a command directly via [`Command`], without going
+/// through a shell.
+///
+/// `Cmd` never hands its arguments to `sh -c`, so there is no quoting or
+/// injection risk: the program is looked up on `PATH` and invoked with
+/// exactly the arguments given. [`exec`] is a thin wrapper that
+/// shlex-parses a command string into a `Cmd`.
+///
+/// # Examples
+/// ```rust
+/// Cmd::new("hyprctl")
+///     .args(["workspaces"])
+///     .cwd(Path::new("/home/user/repo"))
+///     .run();
+/// ```
+pub struct Cmd {
+    program: String,
+    args: Vec<String>,
+    envs: HashMap<String, String>,
+    cwd: Option<PathBuf>,
+    #[cfg(unix)]
+    uid: Option<u32>,
+    #[cfg(unix)]
+    gid: Option<u32>,
+    #[cfg(all(unix, feature = "nightly_setgroups"))]
+    groups: Option<Vec<u32>>,
+    #[cfg(unix)]
+    arg0: Option<String>,
+    #[cfg(unix)]
+    pre_exec: Option<Box<dyn FnMut() -> Result<(), Error> + Send + Sync>>,
+    #[cfg(unix)]
+    create_pidfd: bool,
+}
+
+impl Cmd {
+    /// Starts building a command that runs `program`.
+    pub fn new(program: impl Into<String>) -> Self {
+        Cmd {
+            program: program.into(),
+            args: Vec::new(),
+            envs: HashMap::new(),
+            cwd: None,
+            #[cfg(unix)]
+            uid: None,
+            #[cfg(unix)]
+            gid: None,
+            #[cfg(all(unix, feature = "nightly_setgroups"))]
+            groups: None,
+            #[cfg(unix)]
+            arg0: None,
+            #[cfg(unix)]
+            pre_exec: None,
+            #[cfg(unix)]
+            create_pidfd: false,
+        }
+    }
+
+    /// Appends arguments to pass to the program.
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds environment variables on top of the inherited environment.
+    pub fn envs<I, K, V>(mut self, envs: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.envs
+            .extend(envs.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
+    /// Sets the directory to run the command in. Defaults to the current
+    /// working directory when not set.
+    pub fn cwd(mut self, cwd: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+
+    /// Sets the real and effective uid the child process runs as, for
+    /// dropping privileges before exec.
+    #[cfg(unix)]
+    pub fn uid(mut self, uid: u32) -> Self {
+        self.uid = Some(uid);
+        self
+    }
+
+    /// Sets the real and effective gid the child process runs as.
+    #[cfg(unix)]
+    pub fn gid(mut self, gid: u32) -> Self {
+        self.gid = Some(gid);
+        self
+    }
+
+    /// Replaces the child's supplementary group list via `setgroups`.
+    ///
+    /// `CommandExt::groups` is still gated behind the unstable `setgroups`
+    /// std feature, so this is only compiled in when the crate's own
+    /// `nightly_setgroups` Cargo feature is enabled (off by default), the
+    /// same way `run_with_pidfd` gates on `nightly_pidfd`. Enabling it also
+    /// requires the crate root to carry
+    /// `#![cfg_attr(feature = "nightly_setgroups", feature(setgroups))]`.
+    #[cfg(all(unix, feature = "nightly_setgroups"))]
+    pub fn groups(mut self, groups: impl Into<Vec<u32>>) -> Self {
+        self.groups = Some(groups.into());
+        self
+    }
+
+    /// Overrides `argv[0]` independently of the program path used to look
+    /// up and launch the binary.
+    #[cfg(unix)]
+    pub fn arg0(mut self, arg0: impl Into<String>) -> Self {
+        self.arg0 = Some(arg0.into());
+        self
+    }
+
+    /// Registers a closure to run in the child after `fork` but before the
+    /// target program is executed.
+    ///
+    /// Mirrors `CommandExt::pre_exec` and carries the same safety contract:
+    /// only async-signal-safe operations are sound inside `f`.
+    #[cfg(unix)]
+    pub fn pre_exec<F>(mut self, f: F) -> Self
+    where
+        F: FnMut() -> Result<(), Error> + Send + Sync + 'static,
+    {
+        self.pre_exec = Some(Box::new(f));
+        self
+    }
+
+    /// Requests a pidfd for the spawned child via `CommandExt::create_pidfd`.
+    /// Only honored by `Cmd::run_with_pidfd`, which requires the crate's
+    /// `nightly_pidfd` feature; harmless to set otherwise, it's just
+    /// ignored by `run`/`run_timeout`/`replace`.
+    #[cfg(unix)]
+    pub fn create_pidfd(mut self, yes: bool) -> Self {
+        self.create_pidfd = yes;
+        self
+    }
+
+    /// Applies the uid/gid/groups/argv0/pre_exec attributes configured on
+    /// this builder to a freshly constructed [`Command`].
+    ///
+    /// Takes `&mut self` because `pre_exec` is moved out of the builder:
+    /// running a `Cmd` consumes it (see `run`/`run_timeout`/`replace`), so
+    /// the closure firing exactly once is enforced by the type system
+    /// rather than left as a "second run silently drops it" footgun.
+    #[cfg(unix)]
+    fn apply_unix_attrs(&mut self, command: &mut Command) {
+        use std::os::unix::process::CommandExt;
+
+        if let Some(uid) = self.uid {
+            command.uid(uid);
+        }
+        if let Some(gid) = self.gid {
+            command.gid(gid);
+        }
+        #[cfg(feature = "nightly_setgroups")]
+        if let Some(groups) = &self.groups {
+            command.groups(groups);
+        }
+        if let Some(arg0) = &self.arg0 {
+            command.arg0(arg0);
+        }
+        if let Some(pre_exec) = self.pre_exec.take() {
+            // SAFETY: callers are responsible for only performing
+            // async-signal-safe work inside the closure, per `pre_exec`'s
+            // contract.
+            unsafe {
+                command.pre_exec(pre_exec);
+            }
+        }
+    }
+
+    /// Reconstructs the command line by joining the program and its
+    /// arguments with spaces, for use in error messages. Arguments are not
+    /// quoted, so one containing a space is not distinguishable from two.
+    fn command_line(&self) -> String {
+        std::iter::once(self.program.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn resolved_cwd(&self) -> Result<PathBuf, Error> {
+        match &self.cwd {
+            Some(cwd) => Ok(cwd.clone()),
+            None => current_working_dir(),
+        }
+    }
+
+    /// Runs the command, returning its [`Output`].
+    ///
+    /// Returns a [`CmdError`] embedding the full command line, the working
+    /// directory and the exit code if the binary cannot be found, the
+    /// process cannot be spawned, or it exits with a non-zero status.
+    ///
+    /// Consumes the builder: a `pre_exec` hook (Unix-only) can only ever
+    /// fire once, so running a `Cmd` more than once isn't expressible.
+    #[cfg_attr(not(unix), allow(unused_mut))]
+    pub fn run(mut self) -> Result<Output, CmdError> {
+        let command_line = self.command_line();
+        let cwd = self
+            .resolved_cwd()
+            .map_err(|err| CmdError::spawn(command_line.clone(), PathBuf::new(), err))?;
+
+        if which(&self.program).is_err() {
+            return Err(CmdError::spawn(
+                command_line,
+                cwd,
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!("could not find specified command: {}", self.program),
+                ),
+            ));
+        }
+
+        if !cwd.is_dir() {
+            return Err(CmdError::spawn(
+                command_line,
+                cwd.clone(),
+                Error::new(ErrorKind::NotFound, "specified path is not a directory"),
+            ));
+        }
+
+        let mut command = Command::new(&self.program);
+        command.args(&self.args).envs(&self.envs).current_dir(&cwd);
+        #[cfg(unix)]
+        self.apply_unix_attrs(&mut command);
+
+        let output = command
+            .output()
+            .map_err(|err| CmdError::spawn(command_line.clone(), cwd.clone(), err))?;
+
+        if !output.status.success() {
+            return Err(CmdError::exit_status(
+                command_line,
+                cwd,
+                output.status.code(),
+            ));
+        }
+
+        Ok(output)
+    }
+
+    /// Runs the command, killing it if it has not finished after `timeout`.
+    ///
+    /// Stdout and stderr are piped and drained on background threads so a
+    /// child that floods a pipe can't deadlock us while we wait. A command
+    /// that is killed for running over the deadline returns a [`CmdError`]
+    /// whose `io::Error` conversion carries [`ErrorKind::TimedOut`].
+    ///
+    /// Consumes the builder, same as [`Cmd::run`].
+    #[cfg_attr(not(unix), allow(unused_mut))]
+    pub fn run_timeout(mut self, timeout: Duration) -> Result<Output, CmdError> {
+        use wait_timeout::ChildExt;
+
+        let command_line = self.command_line();
+        let cwd = self
+            .resolved_cwd()
+            .map_err(|err| CmdError::spawn(command_line.clone(), PathBuf::new(), err))?;
+
+        if which(&self.program).is_err() {
+            return Err(CmdError::spawn(
+                command_line,
+                cwd,
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!("could not find specified command: {}", self.program),
+                ),
+            ));
+        }
+
+        if !cwd.is_dir() {
+            return Err(CmdError::spawn(
+                command_line,
+                cwd.clone(),
+                Error::new(ErrorKind::NotFound, "specified path is not a directory"),
+            ));
+        }
+
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .envs(&self.envs)
+            .current_dir(&cwd)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        #[cfg(unix)]
+        self.apply_unix_attrs(&mut command);
+
+        let mut child = command
+            .spawn()
+            .map_err(|err| CmdError::spawn(command_line.clone(), cwd.clone(), err))?;
+
+        let mut stdout = child.stdout.take().expect("stdout was piped");
+        let mut stderr = child.stderr.take().expect("stderr was piped");
+        let stdout_reader = thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stdout.read_to_end(&mut buf);
+            buf
+        });
+        let stderr_reader = thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stderr.read_to_end(&mut buf);
+            buf
+        });
+
+        let status = child
+            .wait_timeout(timeout)
+            .map_err(|err| CmdError::spawn(command_line.clone(), cwd.clone(), err))?;
+
+        let status = match status {
+            Some(status) => status,
+            None => {
+                // Deadline exceeded: kill, reap, and report a timeout rather
+                // than leaving a zombie or blocking forever on wait().
+                let _ = child.kill();
+                let _ = child.wait();
+                let _ = stdout_reader.join();
+                let _ = stderr_reader.join();
+                return Err(CmdError::timed_out(command_line, cwd, timeout));
+            }
+        };
+
+        let stdout = stdout_reader.join().unwrap_or_default();
+        let stderr = stderr_reader.join().unwrap_or_default();
+
+        if !status.success() {
+            return Err(CmdError::exit_status(command_line, cwd, status.code()));
+        }
+
+        Ok(Output {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+
+    /// Like [`Cmd::run`], but also returns the child's pidfd when
+    /// [`Cmd::create_pidfd`] was set to `true`.
+    ///
+    /// Kept separate from `run` because `CommandExt::create_pidfd` and
+    /// `ChildExt::pidfd` are Linux-only and still gated behind the
+    /// unstable `linux_pidfd` feature upstream, so this is itself gated
+    /// behind the crate's `nightly_pidfd` feature (off by default) and
+    /// requires building with nightly and
+    /// `#![cfg_attr(feature = "nightly_pidfd", feature(linux_pidfd))]`
+    /// enabled at the crate root; callers who don't need a pidfd should
+    /// keep using `run`.
+    ///
+    /// Consumes `self`, the same as `run`, since applying the unix
+    /// attributes and `pre_exec` hook consumes the builder.
+    #[cfg(all(unix, target_os = "linux", feature = "nightly_pidfd"))]
+    pub fn run_with_pidfd(
+        mut self,
+    ) -> Result<(Output, Option<std::os::fd::OwnedFd>), CmdError> {
+        use std::os::fd::AsFd;
+        use std::os::linux::process::{ChildExt, CommandExt as LinuxCommandExt};
+
+        let command_line = self.command_line();
+        let cwd = self
+            .resolved_cwd()
+            .map_err(|err| CmdError::spawn(command_line.clone(), PathBuf::new(), err))?;
+
+        if which(&self.program).is_err() {
+            return Err(CmdError::spawn(
+                command_line,
+                cwd,
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!("could not find specified command: {}", self.program),
+                ),
+            ));
+        }
+
+        if !cwd.is_dir() {
+            return Err(CmdError::spawn(
+                command_line,
+                cwd.clone(),
+                Error::new(ErrorKind::NotFound, "specified path is not a directory"),
+            ));
+        }
+
+        let mut command = Command::new(&self.program);
+        // Pipe stdout/stderr as `Command::output` does in `run`, so the
+        // returned `Output` carries what the child printed.
+        command
+            .args(&self.args)
+            .envs(&self.envs)
+            .current_dir(&cwd)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        self.apply_unix_attrs(&mut command);
+        if self.create_pidfd {
+            command.create_pidfd(true);
+        }
+
+        let child = command
+            .spawn()
+            .map_err(|err| CmdError::spawn(command_line.clone(), cwd.clone(), err))?;
+
+        // Duplicate the pidfd before `wait_with_output` consumes and drops
+        // the `Child`, closing its borrowed pidfd out from under us.
+        let pidfd = if self.create_pidfd {
+            child
+                .pidfd()
+                .ok()
+                .and_then(|fd| fd.as_fd().try_clone_to_owned().ok())
+        } else {
+            None
+        };
+
+        let output = child
+            .wait_with_output()
+            .map_err(|err| CmdError::spawn(command_line.clone(), cwd.clone(), err))?;
+
+        if !output.status.success() {
+            return Err(CmdError::exit_status(
+                command_line,
+                cwd,
+                output.status.code(),
+            ));
+        }
+
+        Ok((output, pidfd))
+    }
+
+    fn prepare_for_replace(&self) -> Result<PathBuf, Error> {
+        let cwd = self.resolved_cwd()?;
+
+        if which(&self.program).is_err() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("could not find specified command: {}", self.program),
+            ));
+        }
+
+        if !cwd.is_dir() {
+            return Err(Error::new(ErrorKind::NotFound, "specified path is not a directory"));
+        }
+
+        Ok(cwd)
+    }
+
+    /// Replaces the current process image with this command, `chdir`-ing
+    /// into its working directory first.
+    ///
+    /// On success this never returns: the target inherits our PID and
+    /// signal handling directly, avoiding an extra process in the tree, the
+    /// same "prefer exec" pattern the rust bootstrap `x` launcher uses. It
+    /// only returns when the command could not be resolved or launched.
+    #[cfg(unix)]
+    pub fn replace(mut self) -> Error {
+        use std::os::unix::process::CommandExt;
+
+        let cwd = match self.prepare_for_replace() {
+            Ok(cwd) => cwd,
+            Err(err) => return err,
+        };
+
+        let mut command = Command::new(&self.program);
+        command.args(&self.args).envs(&self.envs).current_dir(&cwd);
+        self.apply_unix_attrs(&mut command);
+        command.exec()
+    }
+
+    /// `execvp` is unavailable on Windows, so this instead spawns the
+    /// command, forwards its stdio, waits for it, and exits the current
+    /// process with its status code - it likewise only returns when the
+    /// command could not be resolved or launched.
+    #[cfg(not(unix))]
+    pub fn replace(self) -> Error {
+        let cwd = match self.prepare_for_replace() {
+            Ok(cwd) => cwd,
+            Err(err) => return err,
+        };
+
+        let status = match Command::new(&self.program)
+            .args(&self.args)
+            .envs(&self.envs)
+            .current_dir(&cwd)
+            .status()
+        {
+            Ok(status) => status,
+            Err(err) => return err,
+        };
+
+        std::process::exit(status.code().unwrap_or(1));
+    }
+}
+
 /// Returns output to give command
 ///
 /// It takes in the command/binary to execute, optional flags and the path
@@ -14,6 +620,14 @@ use which::which;
 /// It will only execute if the provided `cmd` is found on host and `cwd` exists
 /// as a directory.
 ///
+/// Internally this shlex-parses `cmd` into a program and its arguments and
+/// runs them directly via [`Cmd`], so quoted arguments are handled correctly
+/// and no shell is involved. This means shell constructs in `cmd` - pipes
+/// (`|`), `&&`/`||`, `$VAR` expansion, globs, and `>`/`<` redirects - are no
+/// longer interpreted and are instead passed through as literal argv
+/// entries to the binary; callers relying on a shell to evaluate those
+/// should build a `sh -c` invocation explicitly instead.
+///
 /// # Arguments
 ///
 /// * `cmd` - The binary to execute.
@@ -30,40 +644,102 @@ use which::which;
 /// ```
 ///
 pub fn exec(cmd: &str, cwd: &Path) -> Result<Output, Error> {
-    // Check if binary for command exists
-    match which(cmd.split(' ').next().unwrap()) {
-        Ok(_) => (),
-        Err(_) => {
-            return Err(Error::new(
-                ErrorKind::NotFound,
-                format!("Could not find specified command: {}", cmd),
-            ))
-        }
-    }
-    // Check if path exists
-    if !cwd.exists() {
-        return Err(Error::new(ErrorKind::Other, "Specified path is invalid!"));
-    }
-    // path is not a directory
-    if !cwd.is_dir() {
-        return Err(Error::new(
-            ErrorKind::Other,
-            "Specified path is not a directory",
-        ));
-    }
-    // Now execute the command
-    if cfg!(target_os = "windows") {
-        return Command::new("cmd")
-            .current_dir(&cwd.as_os_str())
-            .args(["/C", cmd])
-            .output();
-    } else {
-        return Command::new("sh")
-            .current_dir(&cwd.as_os_str())
-            .arg("-c")
-            .arg(cmd)
-            .output();
+    let parts = shlex::split(cmd)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "could not parse command line"))?;
+    let (program, args) = parts
+        .split_first()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "empty command"))?;
+
+    Cmd::new(program.as_str())
+        .args(args.iter().map(String::as_str))
+        .cwd(cwd)
+        .run()
+        .map_err(Error::from)
+}
+
+/// Like [`exec`], but kills the command if it has not finished within
+/// `timeout`.
+///
+/// This bounds subprocess calls made from loops (prompt renderers,
+/// watchers) that cannot afford to block forever on a hung child. On
+/// timeout the child is sent `SIGKILL` (or terminated, on Windows) and the
+/// returned error's `io::Error` has [`ErrorKind::TimedOut`].
+///
+/// # Examples
+/// ```rust
+/// exec_timeout("hyprctl workspaces", Path::new("/home/user/repo"), Duration::from_secs(5));
+/// ```
+pub fn exec_timeout(cmd: &str, cwd: &Path, timeout: Duration) -> Result<Output, Error> {
+    let parts = shlex::split(cmd)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "could not parse command line"))?;
+    let (program, args) = parts
+        .split_first()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "empty command"))?;
+
+    Cmd::new(program.as_str())
+        .args(args.iter().map(String::as_str))
+        .cwd(cwd)
+        .run_timeout(timeout)
+        .map_err(Error::from)
+}
+
+/// Replaces the current process with `cmd`, run in `cwd`.
+///
+/// Useful when `ha-utils` shells out to a final command that is the whole
+/// point of the call - a dispatcher that ends by running one program - so
+/// there's no reason to keep the parent process alive. On success this
+/// never returns; see [`Cmd::replace`] for the platform-specific behavior.
+///
+/// # Examples
+/// ```rust
+/// exec_replace("hyprctl workspaces", Path::new("/home/user/repo"));
+/// ```
+pub fn exec_replace(cmd: &str, cwd: &Path) -> Error {
+    let parts = match shlex::split(cmd) {
+        Some(parts) => parts,
+        None => return Error::new(ErrorKind::InvalidInput, "could not parse command line"),
+    };
+    let (program, args) = match parts.split_first() {
+        Some(v) => v,
+        None => return Error::new(ErrorKind::InvalidInput, "empty command"),
     };
+
+    Cmd::new(program.as_str())
+        .args(args.iter().map(String::as_str))
+        .cwd(cwd)
+        .replace()
+}
+
+static CWD_CACHE: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Returns the cached, normalized current working directory, populating it
+/// from `std::env::current_dir()` on first use.
+///
+/// Unlike calling `std::env::current_dir()` directly, this keeps returning
+/// the last known-good directory if the real cwd is later deleted or
+/// becomes inaccessible - a real scenario for long-lived daemons - so
+/// `exec` can still resolve a stable logical cwd instead of failing.
+pub fn current_working_dir() -> Result<PathBuf, Error> {
+    if let Some(cached) = CWD_CACHE.read().unwrap().as_ref() {
+        return Ok(cached.clone());
+    }
+
+    let cwd = std::env::current_dir()?.canonicalize()?;
+    *CWD_CACHE.write().unwrap() = Some(cwd.clone());
+    Ok(cwd)
+}
+
+/// Changes the current working directory to `path` and updates the cache
+/// `current_working_dir()` reads from.
+///
+/// `path` is canonicalized before `std::env::set_current_dir` is called, so
+/// the cached value stays valid even if a relative component of `path`
+/// later goes away.
+pub fn set_current_working_dir(path: &Path) -> Result<(), Error> {
+    let canonical = path.canonicalize()?;
+    std::env::set_current_dir(&canonical)?;
+    *CWD_CACHE.write().unwrap() = Some(canonical);
+    Ok(())
 }
 
 /// Returns a Pathbuf of current working dir or the dir if provided.
@@ -75,16 +751,155 @@ pub fn exec(cmd: &str, cwd: &Path) -> Result<Output, Error> {
 /// ```rust
 /// get_pwd(None);
 /// ```
-pub fn get_pwd(dir: Option<&Path>) -> PathBuf {
-    let pwd = match std::env::current_dir() {
-        Ok(v) => PathBuf::from(v),
-        Err(err) => panic!("Couldn't find current dir: {}", err),
-    };
+pub fn get_pwd(dir: Option<&Path>) -> Result<PathBuf, Error> {
+    match dir {
+        Some(v) => Ok(v.to_path_buf()),
+        None => current_working_dir(),
+    }
+}
+
+/// Walks upward from the current working directory toward the filesystem
+/// root, returning the first ancestor whose contents match one of
+/// `markers` (e.g. `.git`, a config filename), or `None` if none match
+/// before reaching the root.
+///
+/// Analogous to how package tooling falls back to locating manifest files
+/// in the current directory tree.
+pub fn find_root(markers: &[&str]) -> Option<PathBuf> {
+    let mut dir = current_working_dir().ok()?;
+
+    loop {
+        if markers.iter().any(|marker| dir.join(marker).exists()) {
+            return Some(dir);
+        }
+
+        if !dir.pop() {
+            return None;
+        }
+    }
+}
+
+/// Like [`get_pwd`], but when no explicit `dir` is given, first searches
+/// for a project root via [`find_root`] and uses that, falling back to the
+/// plain working directory if none of `markers` is found.
+pub fn get_pwd_in_root(dir: Option<&Path>, markers: &[&str]) -> Result<PathBuf, Error> {
+    if dir.is_none() {
+        if let Some(root) = find_root(markers) {
+            return Ok(root);
+        }
+    }
+    get_pwd(dir)
+}
 
-    return match dir {
-        Some(v) => v.to_path_buf(),
-        None => pwd,
+/// Like [`exec`], but runs `cmd` in the project root detected by
+/// [`find_root`] rather than an explicit directory, so tools built on this
+/// crate can run commands relative to a detected project root rather than
+/// wherever they happened to be invoked.
+///
+/// # Examples
+/// ```rust
+/// exec_in_root("cargo build", &[".git", "Cargo.toml"]);
+/// ```
+pub fn exec_in_root(cmd: &str, markers: &[&str]) -> Result<Output, Error> {
+    let cwd = get_pwd_in_root(None, markers)?;
+    exec(cmd, &cwd)
+}
+
+/// A `TestDir` integration-test harness for exercising commands run
+/// through [`exec`] against a real, disposable scratch directory instead of
+/// shelling out to `/tmp` directly.
+///
+/// Available behind the `test-util` feature so downstream crates can write
+/// the same kind of hermetic test this crate uses internally.
+#[cfg(feature = "test-util")]
+pub mod test_util {
+    use std::{
+        fs,
+        io::Error,
+        path::{Path, PathBuf},
+        process::Output,
+        sync::atomic::{AtomicU64, Ordering},
     };
+
+    use super::exec;
+
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+    /// A unique scratch directory for one test, removed on drop.
+    pub struct TestDir {
+        path: PathBuf,
+    }
+
+    impl TestDir {
+        /// Creates a fresh, uniquely-named scratch directory under the
+        /// system temp dir.
+        pub fn new(test_name: &str) -> Result<Self, Error> {
+            let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+            let path = std::env::temp_dir().join(format!(
+                "ha-utils-{}-{}-{}",
+                test_name,
+                std::process::id(),
+                id
+            ));
+            fs::create_dir_all(&path)?;
+            Ok(TestDir { path })
+        }
+
+        /// The scratch directory's path.
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+
+        /// Writes `contents` to `name` inside the scratch directory.
+        pub fn write_fixture(&self, name: &str, contents: &str) -> Result<PathBuf, Error> {
+            let path = self.path.join(name);
+            fs::write(&path, contents)?;
+            Ok(path)
+        }
+
+        /// Runs `cmd` inside the scratch directory via the crate's own
+        /// [`exec`].
+        pub fn run(&self, cmd: &str) -> Result<Output, Error> {
+            exec(cmd, &self.path)
+        }
+
+        /// Asserts that `name` exists inside the scratch directory.
+        pub fn expect_path_exists(&self, name: &str) -> &Self {
+            assert!(
+                self.path.join(name).exists(),
+                "expected `{}` to exist in {}",
+                name,
+                self.path.display()
+            );
+            self
+        }
+
+        /// Asserts that `name`'s contents inside the scratch directory
+        /// equal `expected`.
+        pub fn expect_file_contents(&self, name: &str, expected: &str) -> &Self {
+            let actual = fs::read_to_string(self.path.join(name))
+                .unwrap_or_else(|err| panic!("could not read `{}`: {}", name, err));
+            assert_eq!(actual, expected, "unexpected contents for `{}`", name);
+            self
+        }
+
+        /// Asserts that `output` represents a successful exit.
+        pub fn expect_status_success(&self, output: &Output) -> &Self {
+            assert!(
+                output.status.success(),
+                "expected command run in {} to succeed, got status {:?}",
+                self.path.display(),
+                output.status
+            );
+            self
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -118,4 +933,18 @@ mod tests {
         let cwd = Path::new("/dir/that/does/not/exist");
         exec(cmd, cwd).unwrap();
     }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn test_test_dir_roundtrip() {
+        use super::test_util::TestDir;
+
+        let dir = TestDir::new("cmd_test_dir_roundtrip").unwrap();
+        dir.write_fixture("input.txt", "hello\n").unwrap();
+        let output = dir.run("cat input.txt").unwrap();
+
+        dir.expect_status_success(&output);
+        dir.expect_path_exists("input.txt");
+        dir.expect_file_contents("input.txt", "hello\n");
+    }
 }